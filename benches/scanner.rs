@@ -0,0 +1,108 @@
+//! Throughput benchmarks for [`lox::scanner::Scanner`].
+//!
+//! Each corpus targets a different path through the scanner, so a regression
+//! in one kind of token shows up in its own benchmark.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use lox::interner::Interner;
+use lox::scanner::Scanner;
+
+/// Build a source string by repeating `line` the given number of times.
+fn corpus(line: &str, times: usize) -> String {
+    line.repeat(times)
+}
+
+/// Benchmark scanning `src` with a `Scanner` built by `scanner`, reporting throughput in tokens.
+///
+/// The source is copied for each iteration outside of the timed section,
+/// so only scanning is measured.
+fn bench_corpus(c: &mut Criterion, name: &str, src: String, scanner: impl Fn(String) -> Scanner) {
+    let tokens = scanner(src.clone()).scan().unwrap().len();
+
+    let mut group = c.benchmark_group("scanner");
+    group.throughput(Throughput::Elements(tokens as u64));
+    group.bench_function(name, |b| {
+        b.iter_batched(
+            || src.clone(),
+            |src| scanner(src).scan().unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// Scanning source made up almost entirely of keywords.
+fn keyword_dense(c: &mut Criterion) {
+    let src = corpus(
+        "class fun var if else while for return and or nil true false this super print\n",
+        10_000,
+    );
+    bench_corpus(c, "keyword_dense", src, Scanner::new);
+}
+
+/// Scanning large amounts of numeric data, as found in generated tables.
+fn numeric_data(c: &mut Criterion) {
+    let src = corpus(
+        "var row = 12345.678 + 0.5 * 98765 - 3.14159 / 2.71828 + 42;\n",
+        10_000,
+    );
+    bench_corpus(c, "numeric_data", src, Scanner::new);
+}
+
+/// Scanning source dominated by string literals.
+fn string_heavy(c: &mut Criterion) {
+    let src = corpus(
+        "print \"the quick brown fox jumps over the lazy dog\" + \"again and again\";\n",
+        10_000,
+    );
+    bench_corpus(c, "string_heavy", src, Scanner::new);
+}
+
+/// Scanning source dominated by user defined identifiers.
+fn identifier_heavy(c: &mut Criterion) {
+    let src = corpus(
+        "alpha = beta + gamma * delta - epsilon / zeta + someLongerIdentifierName;\n",
+        10_000,
+    );
+    bench_corpus(c, "identifier_heavy", src, Scanner::new);
+}
+
+/// Scanning identifier heavy source with identifiers interned as symbols.
+///
+/// The interner is dropped with the `Scanner`, since only the cost of scanning is measured.
+fn identifier_heavy_interned(c: &mut Criterion) {
+    let src = corpus(
+        "alpha = beta + gamma * delta - epsilon / zeta + someLongerIdentifierName;\n",
+        10_000,
+    );
+    bench_corpus(c, "identifier_heavy_interned", src, |src| {
+        Scanner::new(src).with_interner(Interner::new())
+    });
+}
+
+/// Scanning source dominated by line comments.
+fn comment_heavy(c: &mut Criterion) {
+    let src = corpus(
+        "// a comment line describing the code below in far too much detail\nx = 1;\n",
+        10_000,
+    );
+    bench_corpus(c, "comment_heavy", src, Scanner::new);
+}
+
+/// Scanning a single very long line, as found in minified or generated source.
+fn long_line(c: &mut Criterion) {
+    let src = corpus("a + b ", 40_000);
+    bench_corpus(c, "long_line", src, Scanner::new);
+}
+
+criterion_group!(
+    benches,
+    keyword_dense,
+    numeric_data,
+    string_heavy,
+    identifier_heavy,
+    identifier_heavy_interned,
+    comment_heavy,
+    long_line
+);
+criterion_main!(benches);
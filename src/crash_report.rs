@@ -0,0 +1,192 @@
+//! Local crash reports for internal errors (panics), enabled with `--crash-report`.
+//!
+//! Reports are only ever written to a file in the system temp directory,
+//! for the user to attach to a bug report if they choose to.
+
+use lox::scanner::Span;
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+thread_local! {
+    /// The phase of execution the interpreter is in, included in crash reports.
+    static PHASE: Cell<&'static str> = const { Cell::new("startup") };
+
+    /// The span of the last token scanned in the current phase, included in crash reports.
+    static SPAN: Cell<Option<Span>> = const { Cell::new(None) };
+}
+
+/// Record the phase of execution the interpreter is entering, e.g. `"scanning"`.
+///
+/// This forgets the span recorded in the previous phase.
+pub fn set_phase(phase: &'static str) {
+    PHASE.with(|current| current.set(phase));
+    SPAN.with(|current| current.set(None));
+}
+
+/// Record the span of the token the interpreter has reached in the source.
+pub fn set_span(span: Span) {
+    SPAN.with(|current| current.set(Some(span)));
+}
+
+/// Install a panic hook that writes a crash report after the usual panic message,
+/// and prints the path of the report.
+///
+/// `script` is the path of the script being run, if any.
+pub fn install(script: Option<PathBuf>) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let location = info
+            .location()
+            .map_or("unknown".to_string(), ToString::to_string);
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(ToString::to_string)
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let report = report(script.as_deref(), &location, &message);
+        match write(&std::env::temp_dir(), &report) {
+            Ok(path) => eprintln!(
+                "lox hit an internal error. A crash report was written to {}",
+                path.display()
+            ),
+            Err(error) => eprintln!("Failed to write crash report: {error}"),
+        }
+    }));
+}
+
+/// Build the text of a crash report for a panic at `location` with the given `message`.
+fn report(script: Option<&Path>, location: &str, message: &str) -> String {
+    let script = script.map_or("<prompt>".to_string(), |path| path.display().to_string());
+    let phase = PHASE.with(Cell::get);
+    let span = SPAN.with(Cell::get).map_or("unknown".to_string(), |span| {
+        format!(
+            "line {}, column {} (bytes {}..{})",
+            span.line,
+            span.column,
+            span.byte_offset,
+            span.byte_offset + span.len
+        )
+    });
+
+    format!(
+        "lox version: {}\nscript: {script}\nphase: {phase}\nlast token: {span}\npanicked at: {location}\nmessage: {message}\n\nbacktrace:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        Backtrace::force_capture(),
+    )
+}
+
+/// How many names to try for a crash report file before giving up.
+const MAX_ATTEMPTS: usize = 100;
+
+/// Write a crash report to a new file in `dir`, returning its path.
+///
+/// Existing files are never opened, so a file (or symlink) already at a report's
+/// path is not overwritten; another name is tried instead.
+fn write(dir: &Path, report: &str) -> std::io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let name = format!("lox-crash-{timestamp}-{}", std::process::id());
+
+    for attempt in 0..MAX_ATTEMPTS {
+        let path = match attempt {
+            0 => dir.join(format!("{name}.txt")),
+            _ => dir.join(format!("{name}-{attempt}.txt")),
+        };
+
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(report.as_bytes())?;
+                return Ok(path);
+            }
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    }
+
+    Err(std::io::Error::new(
+        ErrorKind::AlreadyExists,
+        "every crash report file name tried already exists",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create an empty directory in the temp directory for a test to write reports to.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "lox-crash-report-test-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn write_creates_file() {
+        let dir = test_dir("write");
+
+        let path = write(&dir, "the report").unwrap();
+
+        assert!(path.starts_with(&dir));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "the report");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A file already at a report's path is left alone, and another name is used.
+    #[test]
+    fn write_keeps_existing_files() {
+        let dir = test_dir("existing");
+
+        let first = write(&dir, "first").unwrap();
+        let second = write(&dir, "second").unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "first");
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), "second");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn report_format() {
+        set_phase("scanning");
+        set_span(Span {
+            line: 3,
+            column: 5,
+            byte_offset: 20,
+            len: 4,
+        });
+
+        let report = report(Some(Path::new("script.lox")), "src/main.rs:1:1", "boom");
+
+        let expected = format!(
+            "lox version: {}\nscript: script.lox\nphase: scanning\nlast token: line 3, column 5 (bytes 20..24)\npanicked at: src/main.rs:1:1\nmessage: boom\n\nbacktrace:\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        assert!(
+            report.starts_with(&expected),
+            "unexpected report:\n{report}"
+        );
+    }
+
+    /// Entering a new phase forgets the span from the previous one.
+    #[test]
+    fn report_without_span() {
+        set_phase("reading source");
+
+        let report = report(None, "src/main.rs:1:1", "boom");
+
+        assert!(report.contains("script: <prompt>\nphase: reading source\nlast token: unknown\n"));
+    }
+}
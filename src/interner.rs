@@ -0,0 +1,69 @@
+//! String interning, mapping each distinct string to a small, copyable [`Symbol`].
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// An identifier for a string stored in an [`Interner`].
+///
+/// Symbols from the same `Interner` are equal exactly when their strings are,
+/// so they can be compared and hashed without touching the string itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Stores each distinct string once, handing out a [`Symbol`] for it.
+#[derive(Debug, Default)]
+pub struct Interner {
+    /// Map from each interned string to its symbol.
+    symbols: HashMap<Arc<str>, Symbol>,
+
+    /// The interned strings, indexed by symbol.
+    strings: Vec<Arc<str>>,
+}
+
+impl Interner {
+    /// Create a new, empty `Interner`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the symbol for the given string, interning it if it hasn't been seen before.
+    ///
+    /// Only the first occurrence of a string allocates.
+    ///
+    /// Panics if more than `u32::MAX` distinct strings are interned.
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(s) {
+            return symbol;
+        }
+
+        let symbol = Symbol(u32::try_from(self.strings.len()).expect("too many interned strings"));
+        let s: Arc<str> = s.into();
+        self.strings.push(s.clone());
+        self.symbols.insert(s, symbol);
+        symbol
+    }
+
+    /// Get the string for a symbol produced by this `Interner`.
+    ///
+    /// Panics if the symbol came from a different `Interner`.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+
+    /// Return the number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Return `true` if no strings have been interned.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
@@ -1 +1,3 @@
+pub mod interner;
 pub mod scanner;
+pub mod testing;
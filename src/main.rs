@@ -1,21 +1,53 @@
 use anyhow::Result;
 use anyhow::{bail, Context};
-use lox::scanner::Scanner;
+use lox::scanner::{Scanner, ScannerError, Token};
 use std::fs::read_to_string;
 use std::{io::Write, path::Path};
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+
+mod crash_report;
 
 fn main() -> Result<()> {
-    match std::env::args().count() {
-        1 => run_prompt(),
-        2 => run_file(std::env::args().nth(1).unwrap()),
-        _ => bail!("Usage: lox [script]"),
+    init_tracing();
+
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let crash_report = args.iter().any(|arg| arg == "--crash-report");
+    args.retain(|arg| arg != "--crash-report");
+
+    if crash_report {
+        crash_report::install(args.first().map(Into::into));
+    }
+
+    match args.as_slice() {
+        [] => run_prompt(),
+        [path] => run_file(path),
+        _ => bail!("Usage: lox [--crash-report] [script]"),
     }
 }
 
+/// Install a subscriber that writes diagnostics to stderr, filtered by `RUST_LOG`.
+/// Nothing is logged unless `RUST_LOG` is set, e.g. `RUST_LOG=lox=debug`.
+fn init_tracing() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stderr)
+        .init();
+}
+
 fn run_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    crash_report::set_phase("reading source");
     let src = read_to_string(path).context("Failed to read source from given path")?;
-    let tokens = Scanner::new(src).scan();
-    dbg!(tokens);
+
+    crash_report::set_phase("scanning");
+    match scan(src) {
+        Ok(tokens) => print_tokens(&tokens),
+        Err(errors) => {
+            report_errors(&errors);
+            // exit code for invalid input data, as used by the reference implementation
+            std::process::exit(65);
+        }
+    }
     Ok(())
 }
 
@@ -31,9 +63,17 @@ fn run_prompt() -> Result<()> {
         }
 
         match stdin.read_line(&mut input) {
+            // end of input, e.g. Ctrl-D
+            Ok(0) => {
+                println!();
+                return Ok(());
+            }
             Ok(_) => {
-                let tokens = Scanner::new(input.clone()).scan();
-                dbg!(tokens);
+                crash_report::set_phase("scanning");
+                match scan(input.clone()) {
+                    Ok(tokens) => print_tokens(&tokens),
+                    Err(errors) => report_errors(&errors),
+                }
             }
             Err(error) => eprintln!("Error reading line: {error}"),
         }
@@ -42,3 +82,23 @@ fn run_prompt() -> Result<()> {
         input.clear();
     }
 }
+
+/// Scan `src` into a list of tokens, or the errors found in it,
+/// recording the span of each token for crash reports as it is scanned.
+fn scan(src: String) -> Result<Vec<Token>, Vec<ScannerError>> {
+    Scanner::new(src).scan_with(|token| crash_report::set_span(token.span))
+}
+
+/// Print each token on its own line to stdout, prefixed with its line and column.
+fn print_tokens(tokens: &[Token]) {
+    for token in tokens {
+        println!("{}:{} {:?}", token.span.line, token.span.column, token.kind);
+    }
+}
+
+/// Print each error on its own line to stderr.
+fn report_errors(errors: &[ScannerError]) {
+    for error in errors {
+        eprintln!("Error: {error}");
+    }
+}
@@ -1,10 +1,45 @@
+use crate::interner::{Interner, Symbol};
+use phf::phf_map;
+use std::collections::HashMap;
 use std::fmt::Display;
+use std::io::Read;
+use std::iter::FusedIterator;
 use thiserror::Error;
+use tracing::{debug, instrument, trace};
 
 type Number = f32;
 
-#[derive(Debug)]
-pub enum Token {
+/// The location of a token in the source code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// The line the token starts on, starting from 1.
+    pub line: usize,
+
+    /// The column the token starts at, in characters from the start of the line, starting from 1.
+    pub column: usize,
+
+    /// The byte offset of the start of the token in the source string.
+    pub byte_offset: usize,
+
+    /// The length of the token in bytes.
+    pub len: usize,
+}
+
+/// A token scanned from the source code, along with where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
     // Punctuation / Single character token
     LeftParen,
     RightParen,
@@ -27,11 +62,28 @@ pub enum Token {
     GreaterEqual,
     Less,
     LessEqual,
+    /// `?.`, only scanned when enabled, see [`Scanner::with_nil_safe_operators`].
+    QuestionDot,
+    /// `??`, only scanned when enabled, see [`Scanner::with_nil_safe_operators`].
+    QuestionQuestion,
 
     // Literals
     Identifier(String),
+    /// An identifier interned by the `Scanner`, see [`Scanner::with_interner`].
+    Symbol(Symbol),
     String(String),
-    Number(Number),
+    /// A raw string literal, delimited by triple quotes, with no escape processing.
+    RawString(String),
+    /// The leading part of an interpolated string, up to the first `${`.
+    /// Interpolation is only scanned when enabled, see [`Scanner::with_interpolation`].
+    InterpolationStart(String),
+    /// The part of an interpolated string between a `}` and the next `${`.
+    InterpolationMiddle(String),
+    /// The trailing part of an interpolated string, from the last `}` to the closing quote.
+    InterpolationEnd(String),
+    /// A number literal's value, along with the literal text as written in the source
+    /// (e.g. `2.50`), for tools that need to reproduce it exactly.
+    Number(Number, String),
 
     // Keywords
     And,
@@ -51,61 +103,86 @@ pub enum Token {
     Var,
     While,
 
+    // Extension keywords, only recognized when enabled
+    Break,
+    Continue,
+    Match,
+    Import,
+
     /// End of file
     Eof,
 }
 
-impl Display for Token {
+impl Display for TokenKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Token::Identifier(id) => write!(f, "{id}"),
-            Token::String(s) => {
+            TokenKind::Identifier(id) => write!(f, "{id}"),
+            TokenKind::Symbol(symbol) => write!(f, "{symbol}"),
+            TokenKind::String(s) => {
                 write!(f, "\"{s}\"")
             }
-            Token::Number(n) => {
-                write!(f, "{n}")
+            TokenKind::InterpolationStart(s) => {
+                write!(f, "\"{s}${{")
+            }
+            TokenKind::InterpolationMiddle(s) => {
+                write!(f, "}}{s}${{")
+            }
+            TokenKind::InterpolationEnd(s) => {
+                write!(f, "}}{s}\"")
+            }
+            TokenKind::RawString(s) => {
+                write!(f, "{RAW_STRING_DELIMITER}{s}{RAW_STRING_DELIMITER}")
+            }
+            TokenKind::Number(_, literal) => {
+                write!(f, "{literal}")
             }
 
             _ => write!(
                 f,
                 "{}",
                 match self {
-                    Token::LeftParen => "(",
-                    Token::RightParen => ")",
-                    Token::LeftBrace => "{",
-                    Token::RightBrace => "}",
-                    Token::Comma => ",",
-                    Token::Dot => ".",
-                    Token::Minus => "-",
-                    Token::Plus => "+",
-                    Token::SemiColon => ";",
-                    Token::Star => "*",
-                    Token::Slash => "/",
-                    Token::Bang => "!",
-                    Token::BangEqual => "!=",
-                    Token::Equal => "=",
-                    Token::EqualEqual => "==",
-                    Token::Greater => ">",
-                    Token::GreaterEqual => ">=",
-                    Token::Less => "<",
-                    Token::LessEqual => "<=",
-                    Token::And => "and",
-                    Token::Class => "class",
-                    Token::Else => "else",
-                    Token::False => "false",
-                    Token::Fun => "fun",
-                    Token::For => "for",
-                    Token::If => "if",
-                    Token::Nil => "nil",
-                    Token::Or => "or",
-                    Token::Print => "print",
-                    Token::Return => "return",
-                    Token::Super => "super",
-                    Token::This => "this",
-                    Token::True => "true",
-                    Token::Var => "var",
-                    Token::While => "while",
-                    Token::Eof => "",
+                    TokenKind::LeftParen => "(",
+                    TokenKind::RightParen => ")",
+                    TokenKind::LeftBrace => "{",
+                    TokenKind::RightBrace => "}",
+                    TokenKind::Comma => ",",
+                    TokenKind::Dot => ".",
+                    TokenKind::Minus => "-",
+                    TokenKind::Plus => "+",
+                    TokenKind::SemiColon => ";",
+                    TokenKind::Star => "*",
+                    TokenKind::Slash => "/",
+                    TokenKind::Bang => "!",
+                    TokenKind::BangEqual => "!=",
+                    TokenKind::Equal => "=",
+                    TokenKind::EqualEqual => "==",
+                    TokenKind::Greater => ">",
+                    TokenKind::GreaterEqual => ">=",
+                    TokenKind::Less => "<",
+                    TokenKind::LessEqual => "<=",
+                    TokenKind::QuestionDot => "?.",
+                    TokenKind::QuestionQuestion => "??",
+                    TokenKind::And => "and",
+                    TokenKind::Class => "class",
+                    TokenKind::Else => "else",
+                    TokenKind::False => "false",
+                    TokenKind::Fun => "fun",
+                    TokenKind::For => "for",
+                    TokenKind::If => "if",
+                    TokenKind::Nil => "nil",
+                    TokenKind::Or => "or",
+                    TokenKind::Print => "print",
+                    TokenKind::Return => "return",
+                    TokenKind::Super => "super",
+                    TokenKind::This => "this",
+                    TokenKind::True => "true",
+                    TokenKind::Var => "var",
+                    TokenKind::While => "while",
+                    TokenKind::Break => "break",
+                    TokenKind::Continue => "continue",
+                    TokenKind::Match => "match",
+                    TokenKind::Import => "import",
+                    TokenKind::Eof => "",
                     _ => unreachable!(),
                 }
             ),
@@ -113,13 +190,60 @@ impl Display for Token {
     }
 }
 
-#[derive(Error, Debug)]
+/// Delimiter that opens and closes a raw string literal.
+const RAW_STRING_DELIMITER: &str = "\"\"\"";
+
+/// Table of reserved words, keyed on their lexeme.
+///
+/// Keywords introduced by syntax extensions belong in [`EXTENSION_KEYWORDS`] instead.
+pub static KEYWORDS: phf::Map<&'static str, TokenKind> = phf_map! {
+    "and" => TokenKind::And,
+    "class" => TokenKind::Class,
+    "else" => TokenKind::Else,
+    "false" => TokenKind::False,
+    "fun" => TokenKind::Fun,
+    "for" => TokenKind::For,
+    "if" => TokenKind::If,
+    "nil" => TokenKind::Nil,
+    "or" => TokenKind::Or,
+    "print" => TokenKind::Print,
+    "return" => TokenKind::Return,
+    "super" => TokenKind::Super,
+    "this" => TokenKind::This,
+    "true" => TokenKind::True,
+    "var" => TokenKind::Var,
+    "while" => TokenKind::While,
+};
+
+/// Table of keywords introduced by syntax extensions, keyed on their lexeme.
+///
+/// These are scanned as identifiers unless enabled with [`Scanner::with_keywords`],
+/// so that code written for standard Lox may still use them as names.
+pub static EXTENSION_KEYWORDS: phf::Map<&'static str, TokenKind> = phf_map! {
+    "break" => TokenKind::Break,
+    "continue" => TokenKind::Continue,
+    "match" => TokenKind::Match,
+    "import" => TokenKind::Import,
+};
+
+#[derive(Error, Debug, Clone, PartialEq)]
 pub enum ScannerError {
     #[error("Unknown token at line {0}")]
     UnknownToken(usize),
 
-    #[error("Unterminated string starting at line {0}")]
-    UnterminatedString(usize),
+    #[error(
+        "Unterminated string starting at line {}, column {}, reached end of file at line {}, column {}",
+        start.line,
+        start.column,
+        end.line,
+        end.column
+    )]
+    UnterminatedString {
+        /// The span of the string's opening quote (or triple quote).
+        start: Span,
+        /// The end of file, where the closing quote was expected.
+        end: Span,
+    },
 
     #[error("Invalid number literal at line {0}")]
     InvalidNumber(usize),
@@ -129,58 +253,188 @@ pub struct Scanner {
     /// Source code string.
     src: String,
 
-    /// List of chars representing the source string.
-    chars: Vec<char>,
-
-    /// An index into the source string that indicates the current position of the `Scanner`.
+    /// A byte offset into the source string that indicates the current position of the `Scanner`.
     current: usize,
 
-    /// The start position of the token the `Scanner` is currently processing.
+    /// The byte offset of the start of the token the `Scanner` is currently processing.
     start: usize,
 
     /// The current line number in the source code the `Scanner` is processing.
     line: usize,
 
-    /// Errors collected while scanning the source.
-    errors: Vec<ScannerError>,
+    /// The byte offset of the start of the current line (after a BOM, on the first line).
+    line_start: usize,
+
+    /// The byte offset and column of the last token whose column was found, so the
+    /// next column on the same line is counted on from there rather than from the line start.
+    last_column: (usize, usize),
+
+    /// Whether the `Scanner` has produced its end of file token.
+    finished: bool,
+
+    /// One entry per interpolated expression (`${...}`) the `Scanner` is currently inside,
+    /// holding the span of its string's opening quote, and a count of the braces opened
+    /// within the expression that have not been closed yet.
+    interpolations: Vec<(Span, usize)>,
+
+    /// Whether `${...}` in string literals starts an interpolated expression.
+    interpolation: bool,
+
+    /// Whether the nil-safe operators `?.` and `??` are recognized.
+    nil_safe_operators: bool,
+
+    /// Extension keywords recognized in addition to the standard [`KEYWORDS`].
+    extension_keywords: HashMap<&'static str, TokenKind>,
+
+    /// Interner for identifiers, if the `Scanner` should produce symbols instead of strings.
+    interner: Option<Interner>,
 }
 
 impl Scanner {
     /// Create a new `Scanner` from a source code string.
     pub fn new(src: String) -> Self {
-        let chars = src.chars().collect();
-        Self {
+        let mut scanner = Self {
             src,
-            chars,
             current: 0,
             start: 0,
             line: 1,
-            errors: Default::default(),
+            line_start: 0,
+            last_column: (0, 1),
+            finished: false,
+            interpolations: Default::default(),
+            interpolation: false,
+            nil_safe_operators: false,
+            extension_keywords: Default::default(),
+            interner: None,
+        };
+        scanner.skip_bom();
+        scanner.skip_shebang();
+        scanner
+    }
+
+    /// Create a new `Scanner` from source code read from `reader`, such as a file.
+    ///
+    /// The source is read into a single string, with no other copy of it kept,
+    /// since the `Scanner` slices lexemes out of one contiguous source string.
+    /// Returns an error if reading fails or the source is not valid UTF-8.
+    pub fn from_reader(mut reader: impl Read) -> std::io::Result<Self> {
+        let mut src = String::new();
+        reader.read_to_string(&mut src)?;
+        Ok(Self::new(src))
+    }
+
+    /// Intern identifiers with the given `Interner`, so they are scanned as
+    /// [`TokenKind::Symbol`]s rather than allocating a `String` for each one.
+    ///
+    /// The same `Interner` can be reused across scans, so an identifier maps to the
+    /// same symbol each time it is scanned. [`Scanner::scan`] consumes the `Scanner`
+    /// and its `Interner` with it, so to resolve the symbols afterwards, iterate over
+    /// the `Scanner` with [`Iterator::by_ref`] and take the `Interner` back with
+    /// [`Scanner::into_interner`]:
+    ///
+    /// ```
+    /// use lox::interner::Interner;
+    /// use lox::scanner::Scanner;
+    ///
+    /// let mut scanner = Scanner::new("a = b".into()).with_interner(Interner::new());
+    /// let tokens: Vec<_> = scanner.by_ref().collect();
+    /// let interner = scanner.into_interner().unwrap();
+    /// assert_eq!(interner.len(), 2);
+    /// ```
+    pub fn with_interner(mut self, interner: Interner) -> Self {
+        self.interner = Some(interner);
+        self
+    }
+
+    /// Return the `Interner` identifiers are being interned with, if any.
+    pub fn interner(&self) -> Option<&Interner> {
+        self.interner.as_ref()
+    }
+
+    /// Consume the `Scanner`, returning the `Interner` identifiers were interned with, if any.
+    pub fn into_interner(self) -> Option<Interner> {
+        self.interner
+    }
+
+    /// Enable the named keywords from [`EXTENSION_KEYWORDS`], so the `Scanner`
+    /// recognizes them as keywords rather than identifiers.
+    ///
+    /// Names that are not extension keywords are ignored.
+    pub fn with_keywords<'a>(mut self, names: impl IntoIterator<Item = &'a str>) -> Self {
+        for name in names {
+            if let Some((&keyword, token)) = EXTENSION_KEYWORDS.get_entry(name) {
+                self.extension_keywords.insert(keyword, token.clone());
+            }
         }
+        self
+    }
+
+    /// Scan `${...}` in string literals as interpolated expressions, producing the
+    /// [`TokenKind::InterpolationStart`], [`TokenKind::InterpolationMiddle`] and
+    /// [`TokenKind::InterpolationEnd`] segments around the expression's tokens.
+    ///
+    /// Without this, `${` is ordinary string content, as in standard Lox.
+    pub fn with_interpolation(mut self) -> Self {
+        self.interpolation = true;
+        self
+    }
+
+    /// Recognize the nil-safe operators, scanning `?.` and `??` as
+    /// [`TokenKind::QuestionDot`] and [`TokenKind::QuestionQuestion`].
+    ///
+    /// Without this, `?` is an unknown token, as in standard Lox.
+    pub fn with_nil_safe_operators(mut self) -> Self {
+        self.nil_safe_operators = true;
+        self
     }
 
     /// Scan the source code and produce a list of tokens.
-    pub fn scan(&mut self) -> Vec<Token> {
-        let mut tokens = Vec::new();
+    ///
+    /// Scanning carries on past errors, so that every error in the source is found.
+    /// If there were any, they are returned instead of the tokens.
+    ///
+    /// This consumes the `Scanner`, including any interner set with [`Scanner::with_interner`],
+    /// so the symbols in the returned tokens cannot be resolved. To keep the interner,
+    /// iterate over the `Scanner` with [`Iterator::by_ref`] instead, then call
+    /// [`Scanner::into_interner`].
+    ///
+    /// To scan tokens lazily, one at a time, use the `Scanner` as an [`Iterator`].
+    pub fn scan(self) -> Result<Vec<Token>, Vec<ScannerError>> {
+        self.scan_with(|_| ())
+    }
 
-        // loop through all tokens in the source
-        while !self.at_end() {
-            self.start = self.current;
+    /// Scan the source code and produce a list of tokens, like [`Scanner::scan`],
+    /// calling `on_token` with each token as it is scanned.
+    #[instrument(name = "scan", level = "debug", skip_all, fields(bytes = self.src.len()))]
+    pub fn scan_with(
+        mut self,
+        mut on_token: impl FnMut(&Token),
+    ) -> Result<Vec<Token>, Vec<ScannerError>> {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
 
-            // add token
-            match self.scan_token() {
-                Ok(Some(token)) => tokens.push(token),
-                Ok(None) => (),
-                Err(e) => self.errors.push(e),
+        for result in self.by_ref() {
+            match result {
+                Ok(token) => {
+                    on_token(&token);
+                    tokens.push(token);
+                }
+                Err(e) => errors.push(e),
             }
-
-            // set position to the start of the next token
-            self.advance();
         }
 
-        tokens.push(Token::Eof);
+        debug!(
+            tokens = tokens.len(),
+            errors = errors.len(),
+            lines = self.line,
+            "finished scanning"
+        );
 
-        tokens
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        }
     }
 
     /// Get the token starting at the current position of the `Scanner`.
@@ -192,34 +446,63 @@ impl Scanner {
     /// Assuming the next token is valid, the `Scanner` position will
     /// be moved to the last character of the token.
     /// Whitespace and newlines will be skipped over
-    fn scan_token(&mut self) -> Result<Option<Token>, ScannerError> {
+    fn scan_token(&mut self) -> Result<Option<TokenKind>, ScannerError> {
         match self.peek() {
             // single character tokens
-            '(' => Ok(Some(Token::LeftParen)),
-            ')' => Ok(Some(Token::RightParen)),
-            '{' => Ok(Some(Token::LeftBrace)),
-            '}' => Ok(Some(Token::RightBrace)),
-            ',' => Ok(Some(Token::Comma)),
-            '.' => Ok(Some(Token::Dot)),
-            '-' => Ok(Some(Token::Minus)),
-            '+' => Ok(Some(Token::Plus)),
-            ';' => Ok(Some(Token::SemiColon)),
-            '*' => Ok(Some(Token::Star)),
+            '(' => Ok(Some(TokenKind::LeftParen)),
+            ')' => Ok(Some(TokenKind::RightParen)),
+            '{' => {
+                if let Some((_, depth)) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                Ok(Some(TokenKind::LeftBrace))
+            }
+            '}' => match self.interpolations.last_mut() {
+                // closes an interpolated expression, so the string continues
+                Some((string_start, 0)) => {
+                    let string_start = *string_start;
+                    self.interpolations.pop();
+                    self.string(string_start).map(Some)
+                }
+                Some((_, depth)) => {
+                    *depth -= 1;
+                    Ok(Some(TokenKind::RightBrace))
+                }
+                None => Ok(Some(TokenKind::RightBrace)),
+            },
+            ',' => Ok(Some(TokenKind::Comma)),
+            '.' => Ok(Some(TokenKind::Dot)),
+            '-' => Ok(Some(TokenKind::Minus)),
+            '+' => Ok(Some(TokenKind::Plus)),
+            ';' => Ok(Some(TokenKind::SemiColon)),
+            '*' => Ok(Some(TokenKind::Star)),
             '/' => Ok(self.comment_or_slash()),
 
             // handle two character tokens
             '!' | '=' | '<' | '>' => Ok(self.two_char_token()),
 
+            '?' if self.nil_safe_operators => self.nil_safe_operator().map(Some),
+
             // skip over whitespace
-            ' ' | '\r' | '\t' => Ok(None),
+            ' ' | '\t' => Ok(None),
 
-            // increment line count on \n
-            '\n' => {
+            // a \r followed by \n is part of a \r\n line ending, counted at the \n
+            '\r' if self.match_next('\n') => Ok(None),
+
+            // increment line count on \n, or a lone \r
+            '\n' | '\r' => {
                 self.line += 1;
+                self.line_start = self.current + 1;
                 Ok(None)
             }
 
-            '"' => self.string().map(Some),
+            '"' if self.src[self.current..].starts_with(RAW_STRING_DELIMITER) => {
+                self.raw_string().map(Some)
+            }
+            '"' => {
+                let quote = self.start_span(1);
+                self.string(quote).map(Some)
+            }
 
             c if c.is_ascii_digit() => self.number().map(Some),
 
@@ -229,36 +512,51 @@ impl Scanner {
         }
     }
 
+    /// Skip over a UTF-8 byte order mark at the very start of the source,
+    /// as written by some Windows editors.
+    fn skip_bom(&mut self) {
+        if self.current == 0 && self.src.starts_with('\u{FEFF}') {
+            self.current = '\u{FEFF}'.len_utf8();
+            self.line_start = self.current;
+        }
+    }
+
+    /// Skip over a `#!` interpreter line at the start of the source (after any BOM),
+    /// so Lox scripts can be made executable on Unix.
+    ///
+    /// The shebang is treated like a comment: the `Scanner` position is moved to
+    /// the line ending after it, which is then scanned as usual to keep line numbers correct.
+    fn skip_shebang(&mut self) {
+        let rest = &self.src[self.current..];
+        if self.line == 1 && rest.starts_with("#!") {
+            self.current += rest.find(['\n', '\r']).unwrap_or(rest.len());
+        }
+    }
+
     /// Handle identifier tokens. Should be called when the `Scanner`
     /// is processing an alphanumeric character as the start of a new token.
     ///
-    /// Returns a token representing a keyword if the identifier is a keyword.
-    /// Otherwise, it returns an identifier token.
+    /// Returns a token representing a keyword if the identifier is a standard keyword
+    /// or an enabled extension keyword.
+    /// Otherwise, it returns an identifier token, or a symbol token if the `Scanner` has an interner.
     ///
     /// This will advance the `Scanner` position to the end of the identifier token.
-    fn identifier(&mut self) -> Token {
+    fn identifier(&mut self) -> TokenKind {
         while self.peek_next().is_alphanumeric() {
             self.advance();
         }
-        let identifier = &self.src[self.start..self.current + 1];
-        match identifier {
-            "and" => Token::And,
-            "class" => Token::Class,
-            "else" => Token::Else,
-            "false" => Token::False,
-            "fun" => Token::Fun,
-            "for" => Token::For,
-            "if" => Token::If,
-            "nil" => Token::Nil,
-            "or" => Token::Or,
-            "print" => Token::Print,
-            "return" => Token::Return,
-            "super" => Token::Super,
-            "this" => Token::This,
-            "true" => Token::True,
-            "var" => Token::Var,
-            "while" => Token::While,
-            _ => Token::Identifier(identifier.to_string()),
+        // slice `src` directly rather than through `lexeme`, so the interner can be borrowed mutably
+        let identifier = &self.src[self.start..self.current + self.peek().len_utf8()];
+        if let Some(keyword) = KEYWORDS
+            .get(identifier)
+            .or_else(|| self.extension_keywords.get(identifier))
+        {
+            return keyword.clone();
+        }
+
+        match &mut self.interner {
+            Some(interner) => TokenKind::Symbol(interner.intern(identifier)),
+            None => TokenKind::Identifier(identifier.to_string()),
         }
     }
 
@@ -269,7 +567,7 @@ impl Scanner {
     /// could not be parsed.
     ///
     /// This will advance the `Scanner` position to the end of the number token.
-    fn number(&mut self) -> Result<Token, ScannerError> {
+    fn number(&mut self) -> Result<TokenKind, ScannerError> {
         while self.peek_next().is_ascii_digit() {
             self.advance();
         }
@@ -292,41 +590,116 @@ impl Scanner {
             }
         }
 
-        let num = self.src[self.start..self.current + 1]
-            .parse::<f32>()
-            .unwrap();
-        Ok(Token::Number(num))
+        let literal = self.lexeme();
+        let num = literal.parse::<Number>().unwrap();
+        Ok(TokenKind::Number(num, literal.to_string()))
     }
 
     /// Handle literal string tokens. This function should be called when the `Scanner` is
-    /// currently on a quote character.
+    /// currently on a quote character, or on the `}` that closes an interpolated expression.
+    /// `string_start` is the span of the string's opening quote.
     ///
-    /// Returns the appropriate token,
+    /// Returns the appropriate token, which is one of the interpolation tokens if the
+    /// string contains or continues an interpolated expression,
     /// else a `ScannerError` if there's an error (e.g. unterminated string).
     ///
     /// This will advance the `Scanner` position to the end of the string
-    /// literal token (at the end quote character).
-    fn string(&mut self) -> Result<Token, ScannerError> {
-        let mut delta_lines = 0;
-        while (self.peek_next() != '"') && !self.at_end() {
-            if self.peek() == '\n' {
-                delta_lines += 1;
-            }
+    /// literal token (at the end quote character, or at the `{` of an interpolation).
+    fn string(&mut self, string_start: Span) -> Result<TokenKind, ScannerError> {
+        let continued = self.peek() == '}';
+
+        while (self.peek_next() != '"') && !self.match_interpolation() && !self.at_end() {
             self.advance();
         }
 
-        // advance position to ending quote
+        // advance position to ending quote, or to the '$' starting an interpolation
         self.advance();
 
         if self.at_end() {
-            return Err(ScannerError::UnterminatedString(self.line));
+            return Err(self.unterminated_string(string_start));
         }
 
-        self.line += delta_lines;
-
         // we don't want the quotes to be part of the rust string representation
-        let str_literal = self.src[self.start + 1..self.current].to_string();
-        Ok(Token::String(str_literal))
+        let str_literal = normalize_line_endings(&self.src[self.start + 1..self.current]);
+        self.skip_line_endings(self.start + 1, self.current);
+
+        if self.peek() == '$' {
+            // advance position to the '{' opening the interpolated expression
+            self.advance();
+            self.interpolations.push((string_start, 0));
+
+            if continued {
+                Ok(TokenKind::InterpolationMiddle(str_literal))
+            } else {
+                Ok(TokenKind::InterpolationStart(str_literal))
+            }
+        } else if continued {
+            Ok(TokenKind::InterpolationEnd(str_literal))
+        } else {
+            Ok(TokenKind::String(str_literal))
+        }
+    }
+
+    /// Handle raw string literal tokens. This function should be called when the `Scanner`
+    /// is currently on the first quote of an opening triple quote delimiter.
+    ///
+    /// Raw strings may span multiple lines and contain single quote characters.
+    /// Their contents are taken verbatim, apart from line endings being normalized to `\n`.
+    ///
+    /// Returns the appropriate token,
+    /// else a `ScannerError` if the closing delimiter is never found.
+    ///
+    /// This will advance the `Scanner` position to the end of the raw string
+    /// literal token (at the last quote of the closing delimiter).
+    fn raw_string(&mut self) -> Result<TokenKind, ScannerError> {
+        let content_start = self.start + RAW_STRING_DELIMITER.len();
+        let string_start = self.start_span(RAW_STRING_DELIMITER.len());
+
+        // advance position to the last quote of the opening delimiter
+        self.advance();
+        self.advance();
+
+        let content_end = loop {
+            let next = self.current + self.peek().len_utf8();
+            if next >= self.src.len() {
+                self.current = self.src.len();
+                return Err(self.unterminated_string(string_start));
+            }
+            if self.src[next..].starts_with(RAW_STRING_DELIMITER) {
+                break next;
+            }
+
+            self.advance();
+        };
+
+        // advance position to the last quote of the closing delimiter
+        self.current = content_end + RAW_STRING_DELIMITER.len() - 1;
+
+        let str_literal = normalize_line_endings(&self.src[content_start..content_end]);
+        self.skip_line_endings(content_start, content_end);
+        Ok(TokenKind::RawString(str_literal))
+    }
+
+    /// Build the error for a string with its opening quote at `string_start`
+    /// that runs to the end of the source without being closed.
+    ///
+    /// This moves the `Scanner` line count to the last line of the source.
+    fn unterminated_string(&mut self, string_start: Span) -> ScannerError {
+        self.skip_line_endings(self.start, self.src.len());
+        ScannerError::UnterminatedString {
+            start: string_start,
+            end: self.eof_span(),
+        }
+    }
+
+    /// Move the line count, and the start of the current line, past the line endings
+    /// in the source between the byte offsets `from` and `to`.
+    fn skip_line_endings(&mut self, from: usize, to: usize) {
+        let skipped = &self.src[from..to];
+        self.line += count_line_endings(skipped);
+        if let Some(line_ending) = skipped.rfind(['\n', '\r']) {
+            self.line_start = from + line_ending + 1;
+        }
     }
 
     /// Handle tokens that are two characters long. This function can be called
@@ -337,7 +710,7 @@ impl Scanner {
     /// Returns `None` if none of the one or two character tokens can be matched.
     ///
     /// This function will advance the `Scanner` position to the end of the token (if found).
-    fn two_char_token(&mut self) -> Option<Token> {
+    fn two_char_token(&mut self) -> Option<TokenKind> {
         let first_char = self.peek();
 
         let equal_next = self.match_next('=');
@@ -346,51 +719,128 @@ impl Scanner {
         }
 
         match (first_char, equal_next) {
-            ('!', true) => Some(Token::BangEqual),
-            ('!', false) => Some(Token::Bang),
+            ('!', true) => Some(TokenKind::BangEqual),
+            ('!', false) => Some(TokenKind::Bang),
 
-            ('=', true) => Some(Token::EqualEqual),
-            ('=', false) => Some(Token::Equal),
+            ('=', true) => Some(TokenKind::EqualEqual),
+            ('=', false) => Some(TokenKind::Equal),
 
-            ('>', true) => Some(Token::GreaterEqual),
-            ('>', false) => Some(Token::Greater),
+            ('>', true) => Some(TokenKind::GreaterEqual),
+            ('>', false) => Some(TokenKind::Greater),
 
-            ('<', true) => Some(Token::LessEqual),
-            ('<', false) => Some(Token::Less),
+            ('<', true) => Some(TokenKind::LessEqual),
+            ('<', false) => Some(TokenKind::Less),
 
             _ => None,
         }
     }
 
+    /// Handle the nil-safe operators, `?.` and `??`. This function should be
+    /// called when the `Scanner` is currently on a '?' character.
+    ///
+    /// Returns the appropriate token, or a `ScannerError` if the '?'
+    /// is not followed by '.' or another '?'.
+    ///
+    /// This function will advance the `Scanner` position to the end of the token (if found).
+    fn nil_safe_operator(&mut self) -> Result<TokenKind, ScannerError> {
+        let token = match self.peek_next() {
+            '.' => TokenKind::QuestionDot,
+            '?' => TokenKind::QuestionQuestion,
+            _ => return Err(ScannerError::UnknownToken(self.line)),
+        };
+        self.advance();
+        Ok(token)
+    }
+
     /// Handle a token that starts with a '/'; it may
     /// be the start of a comment or a single slash.
     ///
-    /// Returns the appropriate `Token` if is a single slash,
+    /// Returns the appropriate `TokenKind` if it is a single slash,
     /// and `None` if it is a comment.
     ///
     /// This function will advance the `Scanner` position to the end of the token / comment.
-    fn comment_or_slash(&mut self) -> Option<Token> {
+    fn comment_or_slash(&mut self) -> Option<TokenKind> {
         // check if next token is a comment
         if self.match_next('/') {
-            while (self.peek_next() != '\n') && !self.at_end() {
+            while !matches!(self.peek_next(), '\n' | '\r') && !self.at_end() {
                 self.advance();
             }
             None
         } else {
-            Some(Token::Slash)
+            Some(TokenKind::Slash)
+        }
+    }
+
+    /// Return the span of the token being processed, which starts at the given line
+    /// and column, and ends at the character at the current position.
+    fn span(&self, line: usize, column: usize) -> Span {
+        Span {
+            line,
+            column,
+            byte_offset: self.start,
+            len: self.lexeme().len(),
+        }
+    }
+
+    /// Return the span of the first `len` bytes of the token being processed.
+    /// Should be called before the `Scanner` moves past the token's first line.
+    fn start_span(&mut self, len: usize) -> Span {
+        Span {
+            line: self.line,
+            column: self.column(),
+            byte_offset: self.start,
+            len,
+        }
+    }
+
+    /// Return the empty span at the end of the source.
+    /// Should be called once the `Scanner` line count has reached the last line.
+    fn eof_span(&mut self) -> Span {
+        Span {
+            line: self.line,
+            column: self.column_at(self.src.len()),
+            byte_offset: self.src.len(),
+            len: 0,
         }
     }
 
-    /// Advance the `Scanner`'s current position by one.
+    /// Return the column of the start of the token being processed, counted
+    /// in characters from the start of its line (or the end of a BOM on the first line).
+    fn column(&mut self) -> usize {
+        self.column_at(self.start)
+    }
+
+    /// Return the column of the byte `offset` on the current line.
+    ///
+    /// Only the characters since the last column found on the same line are counted,
+    /// so that finding columns stays linear in the length of long lines.
+    fn column_at(&mut self, offset: usize) -> usize {
+        let (from, column) = match self.last_column {
+            (from, column) if from >= self.line_start => (from, column),
+            _ => (self.line_start, 1),
+        };
+        let column = column + self.src[from..offset].chars().count();
+        self.last_column = (offset, column);
+        column
+    }
+
+    /// Return the source text of the token being processed, from its start
+    /// up to and including the character at the current position.
+    #[inline]
+    fn lexeme(&self) -> &str {
+        &self.src[self.start..self.current + self.peek().len_utf8()]
+    }
+
+    /// Advance the `Scanner`'s current position by one character.
     #[inline]
     fn advance(&mut self) {
-        self.current += 1;
+        self.current += self.peek().len_utf8();
     }
 
     /// Return `true` if the `Scanner` position is past the end of the source code.
     #[inline]
     fn at_end(&self) -> bool {
-        self.current >= self.chars.len()
+        self.current >= self.src.len()
     }
 
     /// Return the character in `self.src` at the current position.
@@ -399,19 +849,28 @@ impl Scanner {
         if self.at_end() {
             '\0'
         } else {
-            self.chars[self.current]
+            self.src[self.current..].chars().next().unwrap_or('\0')
         }
     }
 
     /// Return the character in `self.src` one after the current position.
     fn peek_next(&self) -> char {
-        if (self.current + 1) >= self.chars.len() {
+        if self.at_end() {
             '\0'
         } else {
-            self.chars[self.current + 1]
+            self.src[self.current..].chars().nth(1).unwrap_or('\0')
         }
     }
 
+    /// Return `true` if interpolation is enabled and an interpolated expression (`${`)
+    /// starts after the current `Scanner` position.
+    #[inline]
+    fn match_interpolation(&self) -> bool {
+        self.interpolation
+            && !self.at_end()
+            && self.src[self.current + self.peek().len_utf8()..].starts_with("${")
+    }
+
     /// Inspect the character in `self.src` after the current `Scanner` position.
     /// Returns `true` if it matches the given character, `false` otherwise.
     #[inline]
@@ -423,3 +882,75 @@ impl Scanner {
         }
     }
 }
+
+impl Iterator for Scanner {
+    type Item = Result<Token, ScannerError>;
+
+    /// Scan the next token from the source, or the next error.
+    ///
+    /// The last item is always the end of file token, after which `None` is returned.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        // loop until the next token or error, skipping whitespace and comments
+        while !self.at_end() {
+            self.start = self.current;
+            let line = self.line;
+            let column = self.column();
+
+            let result = match self.scan_token() {
+                Ok(Some(kind)) => {
+                    let token = Token {
+                        kind,
+                        span: self.span(line, column),
+                    };
+                    trace!(line, column = token.span.column, %token, "scanned token");
+                    Some(Ok(token))
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    debug!(error = %e, "scanner error");
+                    Some(Err(e))
+                }
+            };
+
+            // set position to the start of the next token
+            self.advance();
+
+            if result.is_some() {
+                return result;
+            }
+        }
+
+        // an interpolated expression was never closed, so neither was its string
+        if let Some(&(start, _)) = self.interpolations.first() {
+            self.interpolations.clear();
+            return Some(Err(ScannerError::UnterminatedString {
+                start,
+                end: self.eof_span(),
+            }));
+        }
+
+        self.finished = true;
+        self.start = self.src.len();
+        Some(Ok(Token {
+            kind: TokenKind::Eof,
+            span: self.eof_span(),
+        }))
+    }
+}
+
+impl FusedIterator for Scanner {}
+
+/// Convert `\r\n` and lone `\r` line endings in `s` to `\n`, so string
+/// contents are the same whichever platform the source was written on.
+fn normalize_line_endings(s: &str) -> String {
+    s.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Count the line endings (`\n`, `\r\n` or a lone `\r`) in `s`.
+fn count_line_endings(s: &str) -> usize {
+    s.matches('\n').count() + s.matches('\r').count() - s.matches("\r\n").count()
+}
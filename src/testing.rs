@@ -0,0 +1,49 @@
+//! Helpers for writing tests against the crate, for use by
+//! downstream tooling as well as the crate's own tests.
+
+use crate::scanner::{Scanner, ScannerError, Token, TokenKind};
+
+/// Scan the given source string into a list of tokens.
+///
+/// Panics if the source has scanner errors.
+pub fn scan(src: impl Into<String>) -> Vec<Token> {
+    Scanner::new(src.into())
+        .scan()
+        .unwrap_or_else(|errors| panic!("unexpected scanner errors: {errors:?}"))
+}
+
+/// Scan the given source string, returning the errors found.
+///
+/// Panics if the source scans without errors.
+pub fn scan_errors(src: impl Into<String>) -> Vec<ScannerError> {
+    match Scanner::new(src.into()).scan() {
+        Ok(tokens) => panic!("expected scanner errors, found tokens: {tokens:?}"),
+        Err(errors) => errors,
+    }
+}
+
+/// Scan the given source string into a list of token kinds, dropping their spans.
+pub fn scan_kinds(src: impl Into<String>) -> Vec<TokenKind> {
+    scan(src).into_iter().map(|token| token.kind).collect()
+}
+
+/// Assert that scanning a source string produces exactly the given kinds of tokens.
+///
+/// Tokens are written as [`TokenKind`] variant names without the `TokenKind::` prefix.
+/// Variant fields are converted with [`Into`], so string literals can be used
+/// for identifiers and strings.
+///
+/// ```
+/// use lox::assert_tokens;
+///
+/// assert_tokens!("var x = 2;", [Var, Identifier("x"), Equal, Number(2.0, "2"), SemiColon, Eof]);
+/// ```
+#[macro_export]
+macro_rules! assert_tokens {
+    ($src:expr, [$($kind:ident $(($($field:expr),* $(,)?))?),* $(,)?]) => {
+        assert_eq!(
+            $crate::testing::scan_kinds($src),
+            vec![$($crate::scanner::TokenKind::$kind $(($(::std::convert::Into::into($field)),*))?),*],
+        )
+    };
+}
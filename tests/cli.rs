@@ -0,0 +1,146 @@
+//! Tests for the behavior of the `lox` command line interface.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Path to a script in the `tests/fixtures` directory.
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+/// Run the `lox` binary with the given arguments, writing `stdin` to its standard input.
+fn lox(args: &[&str], stdin: &str) -> Output {
+    lox_with_log(args, stdin, None)
+}
+
+/// Run the `lox` binary like [`lox`], with `RUST_LOG` set to `log` if given.
+fn lox_with_log(args: &[&str], stdin: &str, log: Option<&str>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_lox"));
+    match log {
+        Some(log) => command.env("RUST_LOG", log),
+        None => command.env_remove("RUST_LOG"),
+    };
+
+    let mut child = command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn lox");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+
+    child.wait_with_output().unwrap()
+}
+
+/// Running a script prints its tokens with their positions, one per line.
+#[test]
+fn file_mode() {
+    let path = fixture("assignment.lox");
+    let output = lox(&[path.to_str().unwrap()], "");
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "2:1 Var\n2:5 Identifier(\"foo\")\n2:9 Equal\n2:11 Number(2.0, \"2\")\n2:12 SemiColon\n3:1 Eof\n"
+    );
+    assert!(output.stderr.is_empty());
+}
+
+/// Without a script, lines are read from stdin until it is closed.
+#[test]
+fn stdin_mode() {
+    let output = lox(&[], "print nil;\n");
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "> 1:1 Print\n1:7 Nil\n1:10 SemiColon\n2:1 Eof\n> \n"
+    );
+}
+
+#[test]
+fn missing_file() {
+    let path = fixture("does_not_exist.lox");
+    let output = lox(&[path.to_str().unwrap()], "");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Failed to read source from given path"));
+}
+
+#[test]
+fn too_many_arguments() {
+    let output = lox(&["a.lox", "b.lox"], "");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Usage: lox [--crash-report] [script]"));
+}
+
+/// With crash reports enabled, a normal run behaves as usual.
+#[test]
+fn crash_report_flag() {
+    let path = fixture("assignment.lox");
+    let output = lox(&["--crash-report", path.to_str().unwrap()], "");
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .starts_with("2:1 Var\n"));
+    assert!(output.stderr.is_empty());
+}
+
+/// With debug logging, each scan logs a summary and how long it took.
+#[test]
+fn debug_logging() {
+    let path = fixture("assignment.lox");
+    let output = lox_with_log(&[path.to_str().unwrap()], "", Some("lox=debug"));
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("finished scanning"), "stderr: {stderr}");
+    assert!(stderr.contains("time.busy"), "stderr: {stderr}");
+}
+
+/// Scanner errors are reported on stderr, and exit with status 65.
+#[test]
+fn scanner_errors() {
+    let path = fixture("unknown_token.lox");
+    let output = lox(&[path.to_str().unwrap()], "");
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: Unknown token at line 2\n"
+    );
+}
+
+/// Errors in one line at the prompt don't end the session.
+#[test]
+fn stdin_mode_errors() {
+    let output = lox(&[], "@\nnil\n");
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "> > 1:1 Nil\n2:1 Eof\n> \n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: Unknown token at line 1\n"
+    );
+}
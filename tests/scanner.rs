@@ -1,28 +1,45 @@
 //! Tests for the public interface of [`lox::scanner::Scanner`].
 
-use lox::scanner::{Scanner, Token};
-
-/// Helper function to get tokens from given source string.
-#[inline]
-fn scan(src: impl Into<String>) -> Vec<Token> {
-    Scanner::new(src.into()).scan()
-}
+use lox::assert_tokens;
+use lox::interner::Interner;
+use lox::scanner::{Scanner, ScannerError, Span, Token, TokenKind};
+use lox::testing::{scan, scan_errors};
 
 /// Check a list of tokens against an expected list of token kinds and lexemes
-fn check(expected: Vec<(Token, &str)>, actual: Vec<Token>) {
+fn check(expected: Vec<(TokenKind, &str)>, actual: Vec<Token>) {
     assert_eq!(expected.len(), actual.len());
-    for (token, (_kind, lexeme)) in actual.iter().zip(expected.iter()) {
-        assert!(matches!(&token, _kind));
+    for (token, (kind, lexeme)) in actual.iter().zip(expected.iter()) {
+        assert_eq!(&token.kind, kind);
         assert_eq!(token.to_string(), lexeme.to_string());
     }
 }
 
+/// Scan a source string with string interpolation enabled.
+fn scan_interpolated(src: &str) -> Result<Vec<Token>, Vec<ScannerError>> {
+    Scanner::new(src.into()).with_interpolation().scan()
+}
+
+/// Build a span from its line, column, byte offset and length.
+fn span(line: usize, column: usize, byte_offset: usize, len: usize) -> Span {
+    Span {
+        line,
+        column,
+        byte_offset,
+        len,
+    }
+}
+
+/// Drop the spans from a list of tokens.
+fn kinds(tokens: Vec<Token>) -> Vec<TokenKind> {
+    tokens.into_iter().map(|token| token.kind).collect()
+}
+
 /// Empty source string should return no tokens except EOF.
 #[test]
 fn empty_src() {
     let tokens = scan("");
     assert_eq!(tokens.len(), 1);
-    assert!(matches!(tokens[0], Token::Eof));
+    assert!(matches!(tokens[0].kind, TokenKind::Eof));
 }
 
 /// Whitespace should be ignored by scanner
@@ -30,42 +47,66 @@ fn empty_src() {
 fn empty_whitespace() {
     let tokens = scan("    \n\r\t\n\n\n\r\t      \n\t");
     assert_eq!(tokens.len(), 1);
-    assert!(matches!(tokens[0], Token::Eof));
+    assert!(matches!(tokens[0].kind, TokenKind::Eof));
 }
 
 #[test]
 fn var_assignment_num() {
     let expected = vec![
-        (Token::Var, "var"),
-        (Token::Identifier("foo".into()), "foo"),
-        (Token::Equal, "="),
-        (Token::Number(2.0), "2"),
-        (Token::Eof, ""),
+        (TokenKind::Var, "var"),
+        (TokenKind::Identifier("foo".into()), "foo"),
+        (TokenKind::Equal, "="),
+        (TokenKind::Number(2.0, "2".into()), "2"),
+        (TokenKind::Eof, ""),
     ];
 
     check(expected, scan("var foo = 2"));
 }
 
+#[test]
+fn keywords() {
+    assert_tokens!(
+        "and class else false fun for if nil or print return super this true var while",
+        [
+            And, Class, Else, False, Fun, For, If, Nil, Or, Print, Return, Super, This, True, Var,
+            While, Eof
+        ]
+    );
+}
+
+#[test]
+fn keyword_prefixed_identifiers() {
+    assert_tokens!(
+        "android classy variable",
+        [
+            Identifier("android"),
+            Identifier("classy"),
+            Identifier("variable"),
+            Eof
+        ]
+    );
+}
+
 #[test]
 fn two_character_operator() {
     let expected = vec![
-        (Token::Identifier("a".into()), "a"),
-        (Token::BangEqual, "!="),
-        (Token::Identifier("b".into()), "b"),
-        (Token::SemiColon, ";"),
-        (Token::Identifier("b".into()), "b"),
-        (Token::LessEqual, "<="),
-        (Token::Identifier("c".into()), "c"),
-        (Token::SemiColon, ";"),
-        (Token::Identifier("c".into()), "c"),
-        (Token::GreaterEqual, ">="),
-        (Token::Identifier("d".into()), "d"),
-        (Token::SemiColon, ";"),
-        (Token::Identifier("a".into()), "a"),
-        (Token::EqualEqual, "=="),
-        (Token::Identifier("a".into()), "a"),
-        (Token::SemiColon, ";"),
-        (Token::Eof, ""),
+        (TokenKind::Identifier("a".into()), "a"),
+        (TokenKind::BangEqual, "!="),
+        (TokenKind::Identifier("b".into()), "b"),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Identifier("b".into()), "b"),
+        (TokenKind::LessEqual, "<="),
+        (TokenKind::Identifier("c".into()), "c"),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Identifier("c".into()), "c"),
+        (TokenKind::GreaterEqual, ">="),
+        (TokenKind::Identifier("d".into()), "d"),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Identifier("a".into()), "a"),
+        (TokenKind::EqualEqual, "=="),
+        (TokenKind::Identifier("a".into()), "a"),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Eof, ""),
     ];
 
     check(expected, scan("a != b; b <= c; c >= d; a == a;"));
@@ -75,10 +116,10 @@ fn two_character_operator() {
 fn single_line_string() {
     let expected = vec![
         (
-            Token::String("this is a string".into()),
+            TokenKind::String("this is a string".into()),
             "\"this is a string\"",
         ),
-        (Token::Eof, ""),
+        (TokenKind::Eof, ""),
     ];
 
     check(expected, scan("\"this is a string\""))
@@ -88,7 +129,7 @@ fn single_line_string() {
 fn single_line_comment() {
     let tokens = scan("// this is a comment");
     assert_eq!(tokens.len(), 1);
-    assert!(matches!(tokens[0], Token::Eof));
+    assert!(matches!(tokens[0].kind, TokenKind::Eof));
 }
 
 #[test]
@@ -96,5 +137,582 @@ fn ending_comment() {
     let tokens = scan("var foo = 2 // this is a comment");
     // scanning for the expression before comment is handled by another test
     assert_eq!(tokens.len(), 5);
-    assert!(matches!(tokens.iter().last().unwrap(), Token::Eof))
+    assert!(matches!(tokens.iter().last().unwrap().kind, TokenKind::Eof))
+}
+
+/// Multi-byte characters must not throw off the positions of following tokens.
+#[test]
+fn non_ascii_source() {
+    let expected = vec![
+        (TokenKind::Var, "var"),
+        (TokenKind::Identifier("café".into()), "café"),
+        (TokenKind::Equal, "="),
+        (TokenKind::String("héllo wörld".into()), "\"héllo wörld\""),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(expected, scan("var café = \"héllo wörld\";"));
+}
+
+#[test]
+fn raw_string() {
+    let expected = vec![
+        (
+            TokenKind::RawString("say \"hi\"\nto everyone".into()),
+            "\"\"\"say \"hi\"\nto everyone\"\"\"",
+        ),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(expected, scan("\"\"\"say \"hi\"\nto everyone\"\"\";"));
+}
+
+#[test]
+fn empty_raw_string() {
+    let expected = vec![
+        (TokenKind::RawString("".into()), "\"\"\"\"\"\""),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(expected, scan("\"\"\"\"\"\""));
+}
+
+#[test]
+fn unterminated_raw_string() {
+    assert_eq!(
+        scan_errors("\"\"\"never closed\"\"\n\n"),
+        vec![ScannerError::UnterminatedString {
+            start: span(1, 1, 0, 3),
+            end: span(3, 1, 19, 0)
+        }]
+    );
+}
+
+#[test]
+fn interpolated_string() {
+    let expected = vec![
+        (
+            TokenKind::InterpolationStart("Hello, ".into()),
+            "\"Hello, ${",
+        ),
+        (TokenKind::Identifier("name".into()), "name"),
+        (TokenKind::InterpolationEnd("!".into()), "}!\""),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(expected, scan_interpolated("\"Hello, ${name}!\"").unwrap());
+}
+
+#[test]
+fn multiple_interpolations() {
+    let expected = vec![
+        (TokenKind::InterpolationStart("".into()), "\"${"),
+        (TokenKind::Identifier("a".into()), "a"),
+        (TokenKind::InterpolationMiddle(" + ".into()), "} + ${"),
+        (TokenKind::Identifier("b".into()), "b"),
+        (TokenKind::Plus, "+"),
+        (TokenKind::Number(1.0, "1".into()), "1"),
+        (TokenKind::InterpolationEnd("".into()), "}\""),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(expected, scan_interpolated("\"${a} + ${b + 1}\"").unwrap());
+}
+
+/// Strings and braces inside an interpolated expression belong to the expression.
+#[test]
+fn nested_interpolation() {
+    let expected = vec![
+        (TokenKind::InterpolationStart("a".into()), "\"a${"),
+        (TokenKind::LeftBrace, "{"),
+        (TokenKind::RightBrace, "}"),
+        (TokenKind::InterpolationStart("b".into()), "\"b${"),
+        (TokenKind::Identifier("c".into()), "c"),
+        (TokenKind::InterpolationEnd("".into()), "}\""),
+        (TokenKind::InterpolationEnd("d".into()), "}d\""),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(
+        expected,
+        scan_interpolated("\"a${ {} \"b${c}\" }d\"").unwrap(),
+    );
+}
+
+/// Without interpolation enabled, `${` is ordinary string content.
+#[test]
+fn interpolation_disabled() {
+    let expected = vec![
+        (TokenKind::Print, "print"),
+        (TokenKind::String("${".into()), "\"${\""),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(expected, scan("print \"${\";"));
+}
+
+#[test]
+fn nil_safe_operators() {
+    let expected = vec![
+        (TokenKind::Identifier("a".into()), "a"),
+        (TokenKind::QuestionDot, "?."),
+        (TokenKind::Identifier("b".into()), "b"),
+        (TokenKind::QuestionQuestion, "??"),
+        (TokenKind::Identifier("c".into()), "c"),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(
+        expected,
+        Scanner::new("a?.b ?? c".into())
+            .with_nil_safe_operators()
+            .scan()
+            .unwrap(),
+    );
+}
+
+/// The nil-safe operators are unknown tokens unless enabled.
+#[test]
+fn nil_safe_operators_disabled() {
+    assert_eq!(
+        scan_errors("a ?? b\nc?.d"),
+        vec![
+            ScannerError::UnknownToken(1),
+            ScannerError::UnknownToken(1),
+            ScannerError::UnknownToken(2),
+        ]
+    );
+}
+
+/// A `?` not followed by `.` or `?` is still an unknown token when enabled.
+#[test]
+fn lone_question_mark() {
+    assert_eq!(
+        Scanner::new("a ? b".into())
+            .with_nil_safe_operators()
+            .scan(),
+        Err(vec![ScannerError::UnknownToken(1)])
+    );
+}
+
+#[test]
+fn shebang_line() {
+    let expected = vec![
+        (TokenKind::Print, "print"),
+        (TokenKind::Number(1.0, "1".into()), "1"),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(expected, scan("#!/usr/bin/env lox\nprint 1;"));
+}
+
+#[test]
+fn shebang_only() {
+    let tokens = scan("#!/usr/bin/env lox");
+    assert_eq!(tokens.len(), 1);
+    assert!(matches!(tokens[0].kind, TokenKind::Eof));
+}
+
+#[test]
+fn byte_order_mark() {
+    let expected = vec![
+        (TokenKind::Var, "var"),
+        (TokenKind::Identifier("x".into()), "x"),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(expected, scan("\u{FEFF}var x"));
+}
+
+#[test]
+fn byte_order_mark_before_shebang() {
+    let expected = vec![(TokenKind::Nil, "nil"), (TokenKind::Eof, "")];
+
+    check(expected, scan("\u{FEFF}#!/usr/bin/env lox\r\nnil"));
+}
+
+#[test]
+fn windows_line_endings() {
+    let expected = vec![
+        (TokenKind::Var, "var"),
+        (TokenKind::Identifier("a".into()), "a"),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Var, "var"),
+        (TokenKind::Identifier("b".into()), "b"),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(expected.clone(), scan("var a; // comment\r\nvar b;\r\n"));
+    check(expected, scan("var a; // comment\rvar b;\r"));
+}
+
+/// Strings must not keep the \r of a \r\n line ending.
+#[test]
+fn string_line_endings() {
+    let expected = vec![
+        (
+            TokenKind::String("one\ntwo\nthree".into()),
+            "\"one\ntwo\nthree\"",
+        ),
+        (
+            TokenKind::RawString("one\ntwo".into()),
+            "\"\"\"one\ntwo\"\"\"",
+        ),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(
+        expected,
+        scan("\"one\r\ntwo\rthree\" \"\"\"one\r\ntwo\"\"\""),
+    );
+}
+
+#[test]
+fn from_reader() {
+    let tokens = Scanner::from_reader("var x".as_bytes())
+        .unwrap()
+        .scan()
+        .unwrap();
+
+    assert_eq!(
+        kinds(tokens),
+        vec![
+            TokenKind::Var,
+            TokenKind::Identifier("x".into()),
+            TokenKind::Eof
+        ]
+    );
+}
+
+#[test]
+fn from_reader_invalid_utf8() {
+    let error = Scanner::from_reader(&[0xff, 0xfe][..]).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+/// Extension keywords are plain identifiers unless enabled.
+#[test]
+fn extension_keywords_disabled() {
+    assert_tokens!(
+        "break continue match import",
+        [
+            Identifier("break"),
+            Identifier("continue"),
+            Identifier("match"),
+            Identifier("import"),
+            Eof
+        ]
+    );
+}
+
+#[test]
+fn extension_keywords_enabled() {
+    let tokens = kinds(
+        Scanner::new("break continue match import".into())
+            .with_keywords(["break", "import", "not_a_keyword"])
+            .scan()
+            .unwrap(),
+    );
+
+    assert_eq!(
+        tokens,
+        vec![
+            TokenKind::Break,
+            TokenKind::Identifier("continue".into()),
+            TokenKind::Identifier("match".into()),
+            TokenKind::Import,
+            TokenKind::Eof
+        ]
+    );
+}
+
+/// Number tokens keep the literal exactly as it was written.
+#[test]
+fn number_literal_text() {
+    let expected = vec![
+        (TokenKind::Number(2.5, "2.50".into()), "2.50"),
+        (TokenKind::Number(7.0, "007".into()), "007"),
+        (TokenKind::Number(0.0, "0.000".into()), "0.000"),
+        (TokenKind::Eof, ""),
+    ];
+
+    check(expected, scan("2.50 007 0.000"));
+}
+
+/// With an interner, repeated identifiers scan to the same symbol.
+#[test]
+fn interned_identifiers() {
+    let mut scanner = Scanner::new("foo = bar + foo".into()).with_interner(Interner::new());
+    let tokens = kinds(scanner.by_ref().collect::<Result<_, _>>().unwrap());
+    let interner = scanner.into_interner().unwrap();
+
+    let (foo, bar) = match tokens[..] {
+        [TokenKind::Symbol(foo), TokenKind::Equal, TokenKind::Symbol(bar), TokenKind::Plus, TokenKind::Symbol(foo_again), TokenKind::Eof] =>
+        {
+            assert_eq!(foo, foo_again);
+            (foo, bar)
+        }
+        _ => panic!("unexpected tokens: {tokens:?}"),
+    };
+    assert_ne!(foo, bar);
+    assert_eq!(interner.resolve(foo), "foo");
+    assert_eq!(interner.resolve(bar), "bar");
+    assert_eq!(interner.len(), 2);
+}
+
+/// Keywords are never interned.
+#[test]
+fn interned_keywords() {
+    let mut scanner = Scanner::new("var x".into()).with_interner(Interner::new());
+    let tokens = kinds(scanner.by_ref().collect::<Result<_, _>>().unwrap());
+
+    assert!(matches!(
+        tokens[..],
+        [TokenKind::Var, TokenKind::Symbol(_), TokenKind::Eof]
+    ));
+    assert_eq!(scanner.interner().unwrap().len(), 1);
+}
+
+/// Check the spans of a list of tokens against expected (line, column, byte offset, length) tuples.
+fn check_spans(expected: Vec<(usize, usize, usize, usize)>, actual: Vec<Token>) {
+    let actual: Vec<_> = actual
+        .iter()
+        .map(|token| {
+            let Span {
+                line,
+                column,
+                byte_offset,
+                len,
+            } = token.span;
+            (line, column, byte_offset, len)
+        })
+        .collect();
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn spans() {
+    check_spans(
+        vec![
+            (1, 1, 0, 3),   // var
+            (1, 5, 4, 3),   // foo
+            (1, 9, 8, 1),   // =
+            (1, 11, 10, 4), // 2.50
+            (1, 15, 14, 1), // ;
+            (2, 1, 16, 5),  // print
+            (2, 7, 22, 2),  // !=
+            (2, 9, 24, 0),  // EOF
+        ],
+        scan("var foo = 2.50;\nprint !="),
+    );
+}
+
+/// A token after a multi-line string is on the line the string ends on.
+#[test]
+fn spans_after_multi_line_string() {
+    check_spans(
+        vec![
+            (1, 1, 0, 1),  // x
+            (1, 3, 2, 8),  // "a\nb\r\nc"
+            (3, 4, 11, 1), // y
+            (3, 5, 12, 0), // EOF
+        ],
+        scan("x \"a\nb\r\nc\" y"),
+    );
+}
+
+/// Columns count characters, while byte offsets and lengths count bytes.
+#[test]
+fn spans_non_ascii() {
+    check_spans(
+        vec![
+            (1, 1, 0, 7),   // "été"
+            (1, 7, 8, 5),   // café
+            (1, 11, 13, 0), // EOF
+        ],
+        scan("\"été\" café"),
+    );
+}
+
+/// A BOM is not counted as a column.
+#[test]
+fn spans_after_bom() {
+    check_spans(vec![(1, 1, 3, 3), (1, 4, 6, 0)], scan("\u{FEFF}nil"));
+}
+
+/// `\r\n` counts as a single line ending, and a lone `\r` as one too.
+#[test]
+fn spans_after_windows_line_endings() {
+    check_spans(
+        vec![(1, 1, 0, 1), (2, 1, 3, 1), (3, 1, 6, 1), (3, 2, 7, 0)],
+        scan("a\r\nb\r\nc"),
+    );
+    check_spans(vec![(1, 1, 0, 1), (2, 1, 2, 1), (2, 2, 3, 0)], scan("a\rb"));
+}
+
+/// A shebang line is skipped like a comment, so the line after it is still line 2.
+#[test]
+fn spans_after_shebang() {
+    check_spans(
+        vec![(2, 1, 19, 5), (2, 6, 24, 0)],
+        scan("#!/usr/bin/env lox\nprint"),
+    );
+    check_spans(
+        vec![(2, 1, 20, 5), (2, 6, 25, 0)],
+        scan("#!/usr/bin/env lox\r\nprint"),
+    );
+}
+
+#[test]
+fn unknown_token() {
+    assert_eq!(
+        scan_errors("var a = 1;\nvar b = @;"),
+        vec![ScannerError::UnknownToken(2)]
+    );
+}
+
+#[test]
+fn invalid_number() {
+    assert_eq!(scan_errors("1234."), vec![ScannerError::InvalidNumber(1)]);
+}
+
+#[test]
+fn unterminated_string() {
+    assert_eq!(
+        scan_errors("print 1;\n\"abc\ndef"),
+        vec![ScannerError::UnterminatedString {
+            start: span(2, 1, 9, 1),
+            end: span(3, 4, 17, 0)
+        }]
+    );
+}
+
+/// The end of file line counts `\r\n` and lone `\r` line endings after the opening quote.
+#[test]
+fn unterminated_string_line_endings() {
+    assert_eq!(
+        scan_errors("x\r\n\"abc\r\ndef\rghi\r\n"),
+        vec![ScannerError::UnterminatedString {
+            start: span(2, 1, 3, 1),
+            end: span(5, 1, 18, 0)
+        }]
+    );
+}
+
+#[test]
+fn unterminated_string_message() {
+    assert_eq!(
+        ScannerError::UnterminatedString {
+            start: span(2, 3, 10, 1),
+            end: span(5, 4, 30, 0)
+        }
+        .to_string(),
+        "Unterminated string starting at line 2, column 3, reached end of file at line 5, column 4"
+    );
+}
+
+/// An interpolation left open means its string is never closed either.
+#[test]
+fn unterminated_interpolation() {
+    assert_eq!(
+        scan_interpolated("\"a${b\n"),
+        Err(vec![ScannerError::UnterminatedString {
+            start: span(1, 1, 0, 1),
+            end: span(2, 1, 6, 0)
+        }])
+    );
+}
+
+/// A string left open after an interpolation is reported from its opening quote.
+#[test]
+fn unterminated_after_interpolation() {
+    assert_eq!(
+        scan_interpolated("x \"a${b}c"),
+        Err(vec![ScannerError::UnterminatedString {
+            start: span(1, 3, 2, 1),
+            end: span(1, 10, 9, 0)
+        }])
+    );
+}
+
+/// Scanning continues after an error, so every error is reported.
+#[test]
+fn multiple_errors() {
+    assert_eq!(
+        scan_errors("@\n1.\n#"),
+        vec![
+            ScannerError::UnknownToken(1),
+            ScannerError::InvalidNumber(2),
+            ScannerError::UnknownToken(3),
+        ]
+    );
+}
+
+/// The callback passed to `scan_with` sees every token, in order.
+#[test]
+fn scan_with_callback() {
+    let mut seen = Vec::new();
+    let tokens = Scanner::new("print 1;".into())
+        .scan_with(|token| seen.push(token.clone()))
+        .unwrap();
+
+    assert_eq!(seen, tokens);
+}
+
+/// The scanner can be pulled from one token at a time.
+#[test]
+fn iterator() {
+    let mut scanner = Scanner::new("print 1;".into());
+
+    assert_eq!(scanner.next().unwrap().unwrap().kind, TokenKind::Print);
+    assert_eq!(
+        scanner.next().unwrap().unwrap().kind,
+        TokenKind::Number(1.0, "1".into())
+    );
+    assert_eq!(scanner.next().unwrap().unwrap().kind, TokenKind::SemiColon);
+    assert_eq!(scanner.next().unwrap().unwrap().kind, TokenKind::Eof);
+    assert!(scanner.next().is_none());
+    assert!(scanner.next().is_none());
+}
+
+/// Errors are yielded in place, between the tokens around them.
+#[test]
+fn iterator_errors() {
+    let results: Vec<_> = Scanner::new("a @ b \"c".into()).collect();
+
+    assert!(matches!(
+        &results[..],
+        [
+            Ok(Token {
+                kind: TokenKind::Identifier(_),
+                ..
+            }),
+            Err(ScannerError::UnknownToken(1)),
+            Ok(Token {
+                kind: TokenKind::Identifier(_),
+                ..
+            }),
+            Err(ScannerError::UnterminatedString {
+                start: Span {
+                    line: 1,
+                    column: 7,
+                    ..
+                },
+                end: Span {
+                    line: 1,
+                    column: 9,
+                    ..
+                },
+            }),
+            Ok(Token {
+                kind: TokenKind::Eof,
+                ..
+            }),
+        ]
+    ));
 }